@@ -1,25 +1,43 @@
 use bevy::prelude::*;
 use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy_ggrs::prelude::*;
+use ggrs::{PlayerType, SessionBuilder, UdpNonBlockingSocket};
+use std::net::SocketAddr;
 
 const HEX_RADIUS: f32 = 40.0;
 const CHUNK_SIZE: i32 = 7;
 const VIEW_DISTANCE: i32 = 2;
+const WORLD_SEED: u64 = 1337;
+
+// Rollback tuning, mirroring the bevy_ggrs tanks example.
+const FPS: usize = 60;
+const INPUT_DELAY: usize = 2;
+const MAX_PREDICTION_WINDOW: usize = 8;
+
+// Stamina gating, mirroring the Ambition example's stamina+regen systems.
+const MAX_STAMINA: f32 = 100.0;
+const STAMINA_COST_PER_STEP: f32 = 20.0;
+const STAMINA_REGEN_PER_SECOND: f32 = 15.0;
+const STAMINA_FLASH_TICKS: u32 = 6;
 
 #[derive(Component)]
-struct HexTile {
-    q: i32,
-    r: i32,
+struct Player {
+    handle: usize,
 }
 
+// Tags whichever `Player` entity is driven by this peer's own keyboard, as
+// opposed to the one mirroring the remote peer over the rollback session.
 #[derive(Component)]
-struct Player;
+struct LocalPlayer;
 
-#[derive(Component)]
+#[derive(Component, Clone, Copy, PartialEq, Eq, Default)]
 struct PlayerMovement {
-    target_position: Vec3,
-    start_position: Vec3,
-    move_timer: f32,
-    move_duration: f32,
+    target_q: i32,
+    target_r: i32,
+    start_q: i32,
+    start_r: i32,
+    move_ticks: u32,
+    move_duration_ticks: u32,
     is_moving: bool,
 }
 
@@ -32,18 +50,305 @@ struct Chunk {
 #[derive(Component)]
 struct ChunkDisplay;
 
-#[derive(Resource)]
+#[derive(Component)]
+struct StaminaDisplay;
+
+// Movement points that gate hex steps. Deliberately a per-player `Component`
+// rather than a single `Resource`, and regenerated in `GgrsSchedule` rather
+// than `Update`: it's consumed by `apply_rollback_input`, which must
+// reproduce the exact same outcome on every resimulated frame, and a shared
+// `Resource` ticked on wall-clock `Update` would mispredict on rollback.
+#[derive(Component, Clone, Copy, PartialEq, Default)]
+struct Stamina {
+    current: f32,
+    max: f32,
+    regen_per_second: f32,
+    flash_ticks: u32,
+}
+
+impl Stamina {
+    fn new() -> Self {
+        Self {
+            current: MAX_STAMINA,
+            max: MAX_STAMINA,
+            regen_per_second: STAMINA_REGEN_PER_SECOND,
+            flash_ticks: 0,
+        }
+    }
+}
+
+// Rollback state: the hex coordinate a player entity currently occupies.
+// Advanced only inside `GgrsSchedule` so resimulating confirmed inputs
+// reproduces the exact same sequence of positions.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Default)]
 struct PlayerPosition {
     q: i32,
     r: i32,
 }
 
+// Keyed by `morton_key(chunk_q, chunk_r)`, which packs the coordinate pair
+// into one `u64` (spatially close chunks get numerically close keys; the
+// `HashMap` itself keeps no order), so load/unload is a single O(1) lookup
+// instead of a scan over every `Chunk` entity in the world.
 #[derive(Resource, Default)]
 struct LoadedChunks {
-    chunks: std::collections::HashSet<(i32, i32)>,
+    entities: std::collections::HashMap<u64, Entity>,
+}
+
+// Zigzags a signed coordinate into an unsigned one (0, -1, 1, -2, 2, ... ->
+// 0, 1, 2, 3, 4, ...) so negative chunk coordinates interleave correctly.
+fn zigzag_encode(v: i32) -> u32 {
+    ((v << 1) ^ (v >> 31)) as u32
+}
+
+// Spreads the bits of a 32-bit value so every bit has a zero to its right,
+// making room to interleave a second value's bits in between.
+fn spread_bits(v: u32) -> u64 {
+    let mut x = v as u64;
+    x = (x | (x << 16)) & 0x0000FFFF0000FFFF;
+    x = (x | (x << 8)) & 0x00FF00FF00FF00FF;
+    x = (x | (x << 4)) & 0x0F0F0F0F0F0F0F0F;
+    x = (x | (x << 2)) & 0x3333333333333333;
+    x = (x | (x << 1)) & 0x5555555555555555;
+    x
+}
+
+// Morton/Z-order interleave of the two chunk coordinates into one key.
+fn morton_key(chunk_q: i32, chunk_r: i32) -> u64 {
+    spread_bits(zigzag_encode(chunk_q)) | (spread_bits(zigzag_encode(chunk_r)) << 1)
+}
+
+// Which GGRS player handle this process controls locally; the other handle
+// is driven by confirmed/predicted input replayed from the remote peer.
+#[derive(Resource)]
+struct LocalPlayerHandle(usize);
+
+// Holds back chunk spawning and player visibility until the textures those
+// systems draw report settled, so the first rendered frame never shows the placeholder
+// texture or pops in once a PNG finishes decoding.
+#[derive(States, Default, Clone, Copy, Eq, PartialEq, Hash, Debug)]
+enum GameState {
+    #[default]
+    Loading,
+    Playing,
+}
+
+// Preloaded once at startup and cloned from everywhere else, so
+// `load_initial_chunks`/`manage_chunks` stop re-loading the grass texture
+// and re-`materials.add`-ing an identical `ColorMaterial` on every call.
+#[derive(Resource)]
+struct GameAssets {
+    grass_texture: Handle<Image>,
+    character_sprite: Handle<Image>,
+    terrain_textures: std::collections::HashMap<TileType, Handle<Image>>,
+    chunk_material: Handle<ColorMaterial>,
+}
+
+fn load_game_assets(mut commands: Commands, asset_server: Res<AssetServer>, mut materials: ResMut<Assets<ColorMaterial>>) {
+    let grass_texture = asset_server.load("grass_texture.png");
+    let character_sprite = asset_server.load("character_sprite.png");
+
+    let terrain_textures = TILE_TYPES
+        .iter()
+        .map(|&tile_type| (tile_type, asset_server.load(terrain_texture_path(tile_type))))
+        .collect();
+
+    // The chunk mesh still blends one shared texture with a per-vertex tint
+    // (see `terrain_color`); the per-`TileType` PNGs above are preloaded now
+    // so a future texture-atlas pass can swap that tint for a real per-tile
+    // texture without adding a mid-game load stall. Nothing reads them yet,
+    // so `check_assets_loaded` must not gate `Playing` on them.
+    let chunk_material = materials.add(ColorMaterial::from(grass_texture.clone()));
+
+    commands.insert_resource(GameAssets {
+        grass_texture,
+        character_sprite,
+        terrain_textures,
+        chunk_material,
+    });
+}
+
+// An asset counts as settled once it's `Loaded` or once it's given up and
+// gone `Failed` — the latter matters so one missing PNG can't leave
+// `check_assets_loaded` waiting forever and the game stuck on Loading.
+fn asset_settled(asset_server: &AssetServer, id: impl Into<bevy::asset::UntypedAssetId>) -> bool {
+    let id = id.into();
+    asset_server.is_loaded_with_dependencies(id) || matches!(asset_server.load_state(id), bevy::asset::LoadState::Failed(_))
+}
+
+// Polls every frame while `GameState::Loading` and flips to `Playing` once
+// the textures chunks/players actually render have settled. `terrain_textures`
+// is preloaded groundwork for a future texture-atlas pass (see
+// `load_game_assets`) and intentionally isn't part of this gate.
+fn check_assets_loaded(
+    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let base_loaded = asset_settled(&asset_server, game_assets.grass_texture.id())
+        && asset_settled(&asset_server, game_assets.character_sprite.id());
+
+    if base_loaded {
+        next_state.set(GameState::Playing);
+    }
+}
+
+// Bitfield input sent over the network, one bit per hex direction
+// (W/S/A/D/Q/E). `bytemuck::Pod` is required by ggrs to ship it as bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct BoxInput {
+    buttons: u8,
+}
+
+const INPUT_UP: u8 = 1 << 0;
+const INPUT_DOWN: u8 = 1 << 1;
+const INPUT_LEFT: u8 = 1 << 2;
+const INPUT_RIGHT: u8 = 1 << 3;
+const INPUT_UP_LEFT: u8 = 1 << 4;
+const INPUT_UP_RIGHT: u8 = 1 << 5;
+
+struct NetConfig;
+
+impl ggrs::Config for NetConfig {
+    type Input = BoxInput;
+    type State = u8;
+    type Address = SocketAddr;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum TileType {
+    Forest,
+    Hills,
+    Mountains,
+    Fields,
+    Pasture,
+    Desert,
+    Water,
+}
+
+const TILE_TYPES: &[TileType] = &[
+    TileType::Forest,
+    TileType::Hills,
+    TileType::Mountains,
+    TileType::Fields,
+    TileType::Pasture,
+    TileType::Desert,
+    TileType::Water,
+];
+
+fn terrain_texture_path(tile_type: TileType) -> &'static str {
+    match tile_type {
+        TileType::Forest => "forest_texture.png",
+        TileType::Hills => "hills_texture.png",
+        TileType::Mountains => "mountains_texture.png",
+        TileType::Fields => "fields_texture.png",
+        TileType::Pasture => "pasture_texture.png",
+        TileType::Desert => "desert_texture.png",
+        TileType::Water => "water_texture.png",
+    }
+}
+
+// Land tiles weighted like Catan's board, plus water at 5 of the 24 weight
+// units, so roughly one hex in five is water and land makes up the rest.
+const TILE_WEIGHTS: &[(TileType, u32)] = &[
+    (TileType::Forest, 4),
+    (TileType::Hills, 3),
+    (TileType::Mountains, 3),
+    (TileType::Fields, 4),
+    (TileType::Pasture, 4),
+    (TileType::Desert, 1),
+    (TileType::Water, 5),
+];
+
+// Catan's number token distribution (no 7, 2 and 12 are rarest).
+const NUMBER_WEIGHTS: &[u8] = &[2, 3, 3, 4, 4, 5, 5, 6, 6, 8, 8, 9, 9, 10, 10, 11, 11, 12];
+
+// Integer hash combining the world seed with a hex's axial coordinates.
+// Deterministic and order-independent, so unloading and reloading a chunk
+// in `manage_chunks` always regenerates the same tiles.
+fn mix_hash(seed: u64, q: i32, r: i32) -> u64 {
+    let mut h = (q as i64 as u64).wrapping_mul(0x9E3779B1) ^ (r as i64 as u64).wrapping_mul(0x85EBCA77) ^ seed;
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2545F491);
+    h ^= h >> 13;
+    h
+}
+
+fn terrain_at(seed: u64, q: i32, r: i32) -> TileType {
+    let total_weight: u32 = TILE_WEIGHTS.iter().map(|(_, w)| w).sum();
+    let roll = (mix_hash(seed, q, r) % total_weight as u64) as u32;
+
+    let mut acc = 0;
+    for (tile_type, weight) in TILE_WEIGHTS {
+        acc += weight;
+        if roll < acc {
+            return *tile_type;
+        }
+    }
+    TileType::Water
+}
+
+// Desert and water tiles don't produce resources, so they never get a number token.
+fn number_token_at(seed: u64, q: i32, r: i32, tile_type: TileType) -> Option<u8> {
+    if matches!(tile_type, TileType::Desert | TileType::Water) {
+        return None;
+    }
+    let roll = mix_hash(seed.wrapping_add(1), q, r) as usize % NUMBER_WEIGHTS.len();
+    Some(NUMBER_WEIGHTS[roll])
+}
+
+// `--local-port <port> --remote-addr <ip:port> --local-handle <0|1>`
+struct NetArgs {
+    local_port: u16,
+    remote_addr: SocketAddr,
+    local_handle: usize,
+}
+
+fn parse_net_args() -> NetArgs {
+    let args: Vec<String> = std::env::args().collect();
+    let flag = |name: &str, default: &str| -> String {
+        args.iter()
+            .position(|a| a == name)
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+            .unwrap_or_else(|| default.to_string())
+    };
+
+    NetArgs {
+        local_port: flag("--local-port", "7000").parse().expect("--local-port must be a u16"),
+        remote_addr: flag("--remote-addr", "127.0.0.1:7001")
+            .parse()
+            .expect("--remote-addr must be host:port"),
+        local_handle: flag("--local-handle", "0").parse().expect("--local-handle must be 0 or 1"),
+    }
+}
+
+fn build_ggrs_session(net_args: &NetArgs) -> ggrs::P2PSession<NetConfig> {
+    let remote_handle = 1 - net_args.local_handle;
+
+    let mut session_builder = SessionBuilder::<NetConfig>::new()
+        .with_num_players(2)
+        .with_input_delay(INPUT_DELAY)
+        .with_max_prediction_window(MAX_PREDICTION_WINDOW)
+        .expect("max prediction window must be non-zero")
+        .add_player(PlayerType::Local, net_args.local_handle)
+        .expect("failed to register local player")
+        .add_player(PlayerType::Remote(net_args.remote_addr), remote_handle)
+        .expect("failed to register remote player");
+
+    let socket = UdpNonBlockingSocket::bind_to_port(net_args.local_port)
+        .expect("failed to bind local UDP socket");
+
+    session_builder
+        .start_p2p_session(socket)
+        .expect("failed to start GGRS p2p session")
 }
 
 fn main() {
+    let net_args = parse_net_args();
+    let session = build_ggrs_session(&net_args);
+
     App::new()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
@@ -53,10 +358,35 @@ fn main() {
             }),
             ..default()
         }))
-        .insert_resource(PlayerPosition { q: 0, r: 0 })
+        .add_plugins(GgrsPlugin::<NetConfig>::default())
+        .set_rollback_schedule_fps(FPS)
+        .rollback_component_with_copy::<PlayerPosition>()
+        .rollback_component_with_copy::<PlayerMovement>()
+        .rollback_component_with_copy::<Stamina>()
+        .init_state::<GameState>()
+        .insert_resource(LocalPlayerHandle(net_args.local_handle))
         .insert_resource(LoadedChunks::default())
-        .add_systems(Startup, (setup_camera, spawn_player, load_initial_chunks, setup_ui))
-        .add_systems(Update, (handle_input, animate_player_movement, update_camera, manage_chunks, update_chunk_display))
+        .insert_resource(Session::P2P(session))
+        .add_systems(Startup, (setup_camera, setup_ui))
+        // `spawn_players` must run at a frame both peers agree on, so it's
+        // chained straight after `load_game_assets` in `Startup` rather than
+        // gated on `GameState::Playing` (which each peer reaches on its own
+        // schedule once its local assets finish loading). Spawning the
+        // rollback entities at different logical frames per peer would
+        // desync the GGRS snapshot history; `load_initial_chunks` isn't
+        // rollback state, so it's fine to wait for `Playing`.
+        .add_systems(Startup, (load_game_assets, spawn_players).chain())
+        .add_systems(OnEnter(GameState::Playing), (load_initial_chunks, reveal_players))
+        .add_systems(Update, check_assets_loaded.run_if(in_state(GameState::Loading)))
+        .add_systems(ReadInputs, read_local_inputs)
+        .add_systems(
+            GgrsSchedule,
+            (regenerate_stamina, apply_rollback_input, flash_low_stamina_sprite, animate_player_movement).chain(),
+        )
+        .add_systems(
+            Update,
+            (update_camera, manage_chunks, update_chunk_display, update_stamina_display).run_if(in_state(GameState::Playing)),
+        )
         .run();
 }
 
@@ -67,131 +397,151 @@ fn setup_camera(mut commands: Commands) {
 fn load_initial_chunks(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
     mut loaded_chunks: ResMut<LoadedChunks>,
 ) {
-    let hex_mesh = create_perfect_hexagon();
-    let smaller_hex_mesh = create_smaller_hexagon();
-    let mesh_handle = meshes.add(hex_mesh);
-    let smaller_mesh_handle = meshes.add(smaller_hex_mesh);
-    
-    let grass_texture = asset_server.load("grass_texture.png");
-    let grass_material = materials.add(ColorMaterial::from(grass_texture));
-    let border_material = materials.add(ColorMaterial::from(Color::BLACK));
-    
     // Load chunks around player starting position
     for chunk_q in -VIEW_DISTANCE..=VIEW_DISTANCE {
         for chunk_r in -VIEW_DISTANCE..=VIEW_DISTANCE {
             if chunk_q.abs() <= VIEW_DISTANCE && chunk_r.abs() <= VIEW_DISTANCE && (chunk_q + chunk_r).abs() <= VIEW_DISTANCE {
-                load_chunk(
-                    &mut commands,
-                    chunk_q,
-                    chunk_r,
-                    &mesh_handle,
-                    &smaller_mesh_handle,
-                    &grass_material,
-                    &border_material,
-                );
-                loaded_chunks.chunks.insert((chunk_q, chunk_r));
+                let entity = spawn_chunk(&mut commands, &mut meshes, &game_assets.chunk_material, chunk_q, chunk_r);
+                loaded_chunks.entities.insert(morton_key(chunk_q, chunk_r), entity);
             }
         }
     }
 }
 
-fn load_chunk(
-    commands: &mut Commands,
-    chunk_q: i32,
-    chunk_r: i32,
-    mesh_handle: &Handle<Mesh>,
-    smaller_mesh_handle: &Handle<Mesh>,
-    grass_material: &Handle<ColorMaterial>,
-    border_material: &Handle<ColorMaterial>,
+fn terrain_color(tile_type: TileType) -> Color {
+    match tile_type {
+        TileType::Forest => Color::WHITE,
+        TileType::Hills => Color::srgb(0.72, 0.36, 0.2),
+        TileType::Mountains => Color::srgb(0.55, 0.55, 0.58),
+        TileType::Fields => Color::srgb(0.87, 0.73, 0.2),
+        TileType::Pasture => Color::srgb(0.56, 0.78, 0.32),
+        TileType::Desert => Color::srgb(0.86, 0.8, 0.56),
+        TileType::Water => Color::srgb(0.2, 0.45, 0.75),
+    }
+}
+
+// Appends one hexagon's vertices/indices/colors/uvs to the chunk-mesh
+// builder arrays, offsetting the new triangle fan's indices by however many
+// vertices are already in the buffers.
+fn append_hexagon(
+    positions: &mut Vec<[f32; 3]>,
+    colors: &mut Vec<[f32; 4]>,
+    uvs: &mut Vec<[f32; 2]>,
+    indices: &mut Vec<u32>,
+    center: (f32, f32),
+    radius: f32,
+    z: f32,
+    color: Color,
 ) {
+    let base = positions.len() as u32;
+    let rgba = color.to_srgba().to_f32_array();
+
+    positions.push([center.0, center.1, z]);
+    colors.push(rgba);
+    uvs.push([0.5, 0.5]);
+
+    for i in 0..6 {
+        let angle = (i as f32) * std::f32::consts::PI / 3.0;
+        let x = center.0 + radius * angle.cos();
+        let y = center.1 + radius * angle.sin();
+        positions.push([x, y, z]);
+        colors.push(rgba);
+        uvs.push([angle.cos() * 0.5 + 0.5, angle.sin() * 0.5 + 0.5]);
+    }
+
+    for i in 0..6 {
+        let current = base + i + 1;
+        let next = base + if i == 5 { 1 } else { i + 2 };
+        indices.extend_from_slice(&[base, current, next]);
+    }
+}
+
+// Builds the single batched mesh for a chunk: a black border hex and a
+// terrain-colored hex per tile, all baked into one vertex/index buffer so
+// the chunk renders as one draw call instead of ~100 entities.
+fn build_chunk_mesh(chunk_q: i32, chunk_r: i32) -> (Mesh, Vec<(f32, f32, u8)>) {
     let chunk_offset_q = chunk_q * CHUNK_SIZE;
     let chunk_offset_r = chunk_r * CHUNK_SIZE;
-    
+    let border_width = 2.0;
+
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut uvs = Vec::new();
+    let mut indices = Vec::new();
+    let mut numbers = Vec::new();
+
     for local_q in 0..CHUNK_SIZE {
         for local_r in 0..CHUNK_SIZE {
             let q = chunk_offset_q + local_q;
             let r = chunk_offset_r + local_r;
             let (x, y) = hex_to_world(q, r);
-            
-            // Spawn black background hex (full size)
-            commands.spawn((
-                Mesh2d(mesh_handle.clone()),
-                MeshMaterial2d(border_material.clone()),
-                Transform::from_translation(Vec3::new(x, y, -0.1)),
-                Chunk { chunk_q, chunk_r },
-            ));
-            
-            // Spawn smaller grass-textured hex on top
-            commands.spawn((
-                Mesh2d(smaller_mesh_handle.clone()),
-                MeshMaterial2d(grass_material.clone()),
-                Transform::from_translation(Vec3::new(x, y, 0.0)),
-                HexTile { q, r },
-                Chunk { chunk_q, chunk_r },
-            ));
+            let tile_type = terrain_at(WORLD_SEED, q, r);
+
+            append_hexagon(&mut positions, &mut colors, &mut uvs, &mut indices, (x, y), HEX_RADIUS, -0.1, Color::BLACK);
+            append_hexagon(
+                &mut positions,
+                &mut colors,
+                &mut uvs,
+                &mut indices,
+                (x, y),
+                HEX_RADIUS - border_width,
+                0.0,
+                terrain_color(tile_type),
+            );
+
+            if let Some(number) = number_token_at(WORLD_SEED, q, r, tile_type) {
+                numbers.push((x, y, number));
+            }
         }
     }
-}
 
-fn create_perfect_hexagon() -> Mesh {
-    let mut vertices = Vec::new();
-    let mut indices = Vec::new();
-    
-    // Center point
-    vertices.push([0.0, 0.0, 0.0]);
-    
-    // Six vertices of the hexagon (flat-top orientation)
-    for i in 0..6 {
-        let angle = (i as f32) * std::f32::consts::PI / 3.0;
-        let x = HEX_RADIUS * angle.cos();
-        let y = HEX_RADIUS * angle.sin();
-        vertices.push([x, y, 0.0]);
-    }
-    
-    // Create triangular faces from center to each edge
-    for i in 0..6 {
-        let current = i + 1;
-        let next = if i == 5 { 1 } else { i + 2 };
-        indices.extend_from_slice(&[0, current as u32, next as u32]);
-    }
-    
-    Mesh::new(PrimitiveTopology::TriangleList, default())
-        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
-        .with_inserted_indices(Indices::U32(indices))
+    let mesh = Mesh::new(PrimitiveTopology::TriangleList, default())
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+        .with_inserted_indices(Indices::U32(indices));
+
+    (mesh, numbers)
 }
 
-fn create_smaller_hexagon() -> Mesh {
-    let mut vertices = Vec::new();
-    let mut indices = Vec::new();
-    
-    let border_width = 2.0;
-    let smaller_radius = HEX_RADIUS - border_width;
-    
-    // Center point
-    vertices.push([0.0, 0.0, 0.0]);
-    
-    // Six vertices of the smaller hexagon (flat-top orientation)
-    for i in 0..6 {
-        let angle = (i as f32) * std::f32::consts::PI / 3.0;
-        let x = smaller_radius * angle.cos();
-        let y = smaller_radius * angle.sin();
-        vertices.push([x, y, 0.0]);
-    }
-    
-    // Create triangular faces from center to each edge
-    for i in 0..6 {
-        let current = i + 1;
-        let next = if i == 5 { 1 } else { i + 2 };
-        indices.extend_from_slice(&[0, current as u32, next as u32]);
-    }
-    
-    Mesh::new(PrimitiveTopology::TriangleList, default())
-        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
-        .with_inserted_indices(Indices::U32(indices))
+// Spawns the chunk's batched mesh plus one `Text2d` child per number token.
+// The number tokens are children of the mesh entity, so `despawn_recursive`
+// on the returned entity is required to tear down the whole chunk — a plain
+// `despawn` leaves the children behind.
+fn spawn_chunk(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    chunk_material: &Handle<ColorMaterial>,
+    chunk_q: i32,
+    chunk_r: i32,
+) -> Entity {
+    let (mesh, numbers) = build_chunk_mesh(chunk_q, chunk_r);
+    let mesh_handle = meshes.add(mesh);
+
+    commands
+        .spawn((
+            Mesh2d(mesh_handle),
+            MeshMaterial2d(chunk_material.clone()),
+            Transform::IDENTITY,
+            Chunk { chunk_q, chunk_r },
+        ))
+        .with_children(|chunk| {
+            for (x, y, number) in numbers {
+                chunk.spawn((
+                    Text2d::new(number.to_string()),
+                    TextFont {
+                        font_size: 18.0,
+                        ..default()
+                    },
+                    TextColor(Color::BLACK),
+                    Transform::from_translation(Vec3::new(x, y, 0.1)),
+                ));
+            }
+        })
+        .id()
 }
 
 fn hex_to_world(q: i32, r: i32) -> (f32, f32) {
@@ -201,86 +551,155 @@ fn hex_to_world(q: i32, r: i32) -> (f32, f32) {
     (x, y)
 }
 
-fn spawn_player(mut commands: Commands, asset_server: Res<AssetServer>) {
+// One entity per GGRS handle so both peers see each other move on the
+// shared hex grid; only the entity matching `LocalPlayerHandle` reads this
+// process's keyboard.
+// Spawned hidden: the entities must exist from frame 0 so both peers' GGRS
+// snapshots agree (see the `Startup` scheduling note in `main`), but
+// `character_sprite` may still be mid-load at that point. `reveal_players`
+// un-hides them once `GameState::Playing` confirms the texture is ready, so
+// the pop-in `GameState` was built to avoid doesn't sneak back in here.
+fn spawn_players(
+    mut commands: Commands,
+    game_assets: Res<GameAssets>,
+    local_handle: Res<LocalPlayerHandle>,
+) {
     let (x, y) = hex_to_world(0, 0);
-    let position = Vec3::new(x, y, 1.0);
-    
-    commands.spawn((
-        Sprite {
-            image: asset_server.load("character_sprite.png"),
-            custom_size: Some(Vec2::new(50.0, 50.0)),
-            ..default()
-        },
-        Transform::from_translation(position),
-        Player,
-        PlayerMovement {
-            target_position: position,
-            start_position: position,
-            move_timer: 0.0,
-            move_duration: 0.3,
-            is_moving: false,
-        },
-    ));
+
+    for handle in 0..2 {
+        let mut entity = commands.spawn((
+            Sprite {
+                image: game_assets.character_sprite.clone(),
+                custom_size: Some(Vec2::new(50.0, 50.0)),
+                ..default()
+            },
+            Transform::from_translation(Vec3::new(x, y, 1.0)),
+            Visibility::Hidden,
+            Player { handle },
+            PlayerPosition { q: 0, r: 0 },
+            PlayerMovement::default(),
+            Stamina::new(),
+        ));
+        entity.add_rollback();
+        if handle == local_handle.0 {
+            entity.insert(LocalPlayer);
+        }
+    }
+}
+
+fn reveal_players(mut player_query: Query<&mut Visibility, With<Player>>) {
+    for mut visibility in &mut player_query {
+        *visibility = Visibility::Visible;
+    }
 }
 
-fn handle_input(
+// Packs this frame's keyboard state into the `BoxInput` GGRS ships to the
+// remote peer; `apply_rollback_input` is what actually moves anyone.
+fn read_local_inputs(
+    mut commands: Commands,
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut player_pos: ResMut<PlayerPosition>,
-    mut player_query: Query<(&mut PlayerMovement, &mut Sprite), With<Player>>,
+    local_players: Res<LocalPlayers>,
 ) {
-    if let Ok((mut movement, mut sprite)) = player_query.get_single_mut() {
-        // Don't handle input if already moving
+    let mut local_inputs = std::collections::HashMap::new();
+
+    for handle in &local_players.0 {
+        let mut buttons = 0u8;
+        if keyboard_input.pressed(KeyCode::KeyW) {
+            buttons |= INPUT_UP;
+        }
+        if keyboard_input.pressed(KeyCode::KeyS) {
+            buttons |= INPUT_DOWN;
+        }
+        if keyboard_input.pressed(KeyCode::KeyA) {
+            buttons |= INPUT_LEFT;
+        }
+        if keyboard_input.pressed(KeyCode::KeyD) {
+            buttons |= INPUT_RIGHT;
+        }
+        if keyboard_input.pressed(KeyCode::KeyQ) {
+            buttons |= INPUT_UP_LEFT;
+        }
+        if keyboard_input.pressed(KeyCode::KeyE) {
+            buttons |= INPUT_UP_RIGHT;
+        }
+        local_inputs.insert(*handle, BoxInput { buttons });
+    }
+
+    commands.insert_resource(LocalInputs::<NetConfig>(local_inputs));
+}
+
+// Refills stamina towards `max` at a fixed per-tick rate. Runs before
+// `apply_rollback_input` each rollback tick so a step is always judged
+// against the freshest value.
+fn regenerate_stamina(mut stamina_query: Query<&mut Stamina>) {
+    for mut stamina in &mut stamina_query {
+        stamina.current = (stamina.current + stamina.regen_per_second / FPS as f32).min(stamina.max);
+        stamina.flash_ticks = stamina.flash_ticks.saturating_sub(1);
+    }
+}
+
+// Advances every player deterministically from confirmed/predicted input.
+// Runs inside `GgrsSchedule` so a resimulated frame reaches the same
+// `PlayerPosition` the first time it ran.
+fn apply_rollback_input(
+    inputs: Res<PlayerInputs<NetConfig>>,
+    mut player_query: Query<(&Player, &mut PlayerPosition, &mut PlayerMovement, &mut Stamina, &mut Sprite)>,
+) {
+    for (player, mut position, mut movement, mut stamina, mut sprite) in &mut player_query {
         if movement.is_moving {
-            return;
+            continue;
         }
-        
-        let mut new_q = player_pos.q;
-        let mut new_r = player_pos.r;
-        let mut moved = false;
-        
-        if keyboard_input.just_pressed(KeyCode::KeyW) {
+
+        let (input, _status) = inputs[player.handle];
+        let buttons = input.buttons;
+
+        let mut new_q = position.q;
+        let mut new_r = position.r;
+
+        if buttons & INPUT_UP != 0 {
             new_r += 1;
-            moved = true;
-        } else if keyboard_input.just_pressed(KeyCode::KeyS) {
+        } else if buttons & INPUT_DOWN != 0 {
             new_r -= 1;
-            moved = true;
-        } else if keyboard_input.just_pressed(KeyCode::KeyA) {
+        } else if buttons & INPUT_LEFT != 0 {
             new_q -= 1;
-            moved = true;
-            // Face left (default sprite direction)
             sprite.flip_x = false;
-        } else if keyboard_input.just_pressed(KeyCode::KeyD) {
+        } else if buttons & INPUT_RIGHT != 0 {
             new_q += 1;
-            moved = true;
-            // Face right (flip sprite)
             sprite.flip_x = true;
-        } else if keyboard_input.just_pressed(KeyCode::KeyQ) {
+        } else if buttons & INPUT_UP_LEFT != 0 {
             new_q -= 1;
             new_r += 1;
-            moved = true;
-            // Face left for diagonal left movement
             sprite.flip_x = false;
-        } else if keyboard_input.just_pressed(KeyCode::KeyE) {
+        } else if buttons & INPUT_UP_RIGHT != 0 {
             new_q += 1;
             new_r -= 1;
-            moved = true;
-            // Face right for diagonal right movement
             sprite.flip_x = true;
+        } else {
+            continue;
         }
-        
-        if moved && is_valid_hex(new_q, new_r) && (new_q != player_pos.q || new_r != player_pos.r) {
-            player_pos.q = new_q;
-            player_pos.r = new_r;
-            
-            let (x, y) = hex_to_world(new_q, new_r);
-            let target_position = Vec3::new(x, y, 1.0);
-            
-            // Start movement animation
-            movement.start_position = movement.target_position;
-            movement.target_position = target_position;
-            movement.move_timer = 0.0;
-            movement.is_moving = true;
+
+        if !is_valid_hex(new_q, new_r) || (new_q == position.q && new_r == position.r) {
+            continue;
         }
+
+        if stamina.current < STAMINA_COST_PER_STEP {
+            // Not enough stamina to take the step; flash the sprite instead of moving.
+            stamina.flash_ticks = STAMINA_FLASH_TICKS;
+            continue;
+        }
+
+        stamina.current -= STAMINA_COST_PER_STEP;
+
+        movement.start_q = position.q;
+        movement.start_r = position.r;
+        movement.target_q = new_q;
+        movement.target_r = new_r;
+        movement.move_ticks = 0;
+        movement.move_duration_ticks = (FPS as f32 * 0.3) as u32;
+        movement.is_moving = true;
+
+        position.q = new_q;
+        position.r = new_r;
     }
 }
 
@@ -289,33 +708,50 @@ fn is_valid_hex(_q: i32, _r: i32) -> bool {
     true
 }
 
-fn animate_player_movement(
-    time: Res<Time>,
-    mut player_query: Query<(&mut Transform, &mut PlayerMovement), With<Player>>,
-) {
-    if let Ok((mut transform, mut movement)) = player_query.get_single_mut() {
-        if movement.is_moving {
-            movement.move_timer += time.delta_secs();
-            
-            if movement.move_timer >= movement.move_duration {
-                // Movement complete
-                transform.translation = movement.target_position;
-                movement.is_moving = false;
-                movement.move_timer = 0.0;
-            } else {
-                // Interpolate position with smooth easing
-                let t = movement.move_timer / movement.move_duration;
-                // Use smoothstep for nice easing
-                let smooth_t = t * t * (3.0 - 2.0 * t);
-                
-                transform.translation = movement.start_position.lerp(movement.target_position, smooth_t);
-            }
+// Tints the sprite red for `STAMINA_FLASH_TICKS` after a move is rejected
+// for lack of stamina, then settles back to white.
+fn flash_low_stamina_sprite(mut player_query: Query<(&Stamina, &mut Sprite)>) {
+    for (stamina, mut sprite) in &mut player_query {
+        sprite.color = if stamina.flash_ticks > 0 {
+            Color::srgb(1.0, 0.3, 0.3)
+        } else {
+            Color::WHITE
+        };
+    }
+}
+
+// Interpolates the sprite towards `PlayerMovement`'s target hex one fixed
+// tick at a time, so the on-screen lerp replays identically on rollback
+// instead of depending on wall-clock frame timing.
+fn animate_player_movement(mut player_query: Query<(&mut Transform, &mut PlayerMovement)>) {
+    for (mut transform, mut movement) in &mut player_query {
+        if !movement.is_moving {
+            continue;
+        }
+
+        movement.move_ticks += 1;
+
+        let (start_x, start_y) = hex_to_world(movement.start_q, movement.start_r);
+        let (target_x, target_y) = hex_to_world(movement.target_q, movement.target_r);
+        let start_position = Vec3::new(start_x, start_y, 1.0);
+        let target_position = Vec3::new(target_x, target_y, 1.0);
+
+        if movement.move_ticks >= movement.move_duration_ticks {
+            transform.translation = target_position;
+            movement.is_moving = false;
+            movement.move_ticks = 0;
+        } else {
+            let t = movement.move_ticks as f32 / movement.move_duration_ticks as f32;
+            // Use smoothstep for nice easing
+            let smooth_t = t * t * (3.0 - 2.0 * t);
+
+            transform.translation = start_position.lerp(target_position, smooth_t);
         }
     }
 }
 
 fn update_camera(
-    player_query: Query<&Transform, With<Player>>,
+    player_query: Query<&Transform, (With<Player>, With<LocalPlayer>)>,
     mut camera_query: Query<&mut Transform, (With<Camera2d>, Without<Player>)>,
 ) {
     if let Ok(player_transform) = player_query.get_single() {
@@ -328,66 +764,52 @@ fn update_camera(
 fn manage_chunks(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-    asset_server: Res<AssetServer>,
-    player_pos: Res<PlayerPosition>,
+    game_assets: Res<GameAssets>,
+    local_player_query: Query<&PlayerPosition, With<LocalPlayer>>,
     mut loaded_chunks: ResMut<LoadedChunks>,
-    chunk_query: Query<(Entity, &Chunk)>,
 ) {
+    let Ok(player_pos) = local_player_query.get_single() else {
+        return;
+    };
     let player_chunk_q = if player_pos.q >= 0 { player_pos.q / CHUNK_SIZE } else { (player_pos.q - CHUNK_SIZE + 1) / CHUNK_SIZE };
     let player_chunk_r = if player_pos.r >= 0 { player_pos.r / CHUNK_SIZE } else { (player_pos.r - CHUNK_SIZE + 1) / CHUNK_SIZE };
-    
+
     // Determine which chunks should be loaded
     let mut required_chunks = std::collections::HashSet::new();
     for chunk_q in (player_chunk_q - VIEW_DISTANCE)..=(player_chunk_q + VIEW_DISTANCE) {
         for chunk_r in (player_chunk_r - VIEW_DISTANCE)..=(player_chunk_r + VIEW_DISTANCE) {
-            if (chunk_q - player_chunk_q).abs() <= VIEW_DISTANCE && 
+            if (chunk_q - player_chunk_q).abs() <= VIEW_DISTANCE &&
                (chunk_r - player_chunk_r).abs() <= VIEW_DISTANCE &&
                ((chunk_q - player_chunk_q) + (chunk_r - player_chunk_r)).abs() <= VIEW_DISTANCE {
                 required_chunks.insert((chunk_q, chunk_r));
             }
         }
     }
-    
-    // Unload chunks that are too far away
-    let chunks_to_unload: Vec<(i32, i32)> = loaded_chunks.chunks
-        .iter()
-        .filter(|&&chunk| !required_chunks.contains(&chunk))
+    let required_keys: std::collections::HashSet<u64> =
+        required_chunks.iter().map(|&(q, r)| morton_key(q, r)).collect();
+
+    // Unload chunks that are too far away: O(1) removal per chunk instead of
+    // scanning every `Chunk` entity in the world.
+    let keys_to_unload: Vec<u64> = loaded_chunks
+        .entities
+        .keys()
+        .filter(|key| !required_keys.contains(key))
         .copied()
         .collect();
-    
-    for (chunk_q, chunk_r) in chunks_to_unload {
-        // Remove all entities belonging to this chunk
-        for (entity, chunk) in chunk_query.iter() {
-            if chunk.chunk_q == chunk_q && chunk.chunk_r == chunk_r {
-                commands.entity(entity).despawn();
-            }
+
+    for key in keys_to_unload {
+        if let Some(entity) = loaded_chunks.entities.remove(&key) {
+            commands.entity(entity).despawn_recursive();
         }
-        loaded_chunks.chunks.remove(&(chunk_q, chunk_r));
     }
-    
-    // Load new chunks
-    let hex_mesh = create_perfect_hexagon();
-    let smaller_hex_mesh = create_smaller_hexagon();
-    let mesh_handle = meshes.add(hex_mesh);
-    let smaller_mesh_handle = meshes.add(smaller_hex_mesh);
-    
-    let grass_texture = asset_server.load("grass_texture.png");
-    let grass_material = materials.add(ColorMaterial::from(grass_texture));
-    let border_material = materials.add(ColorMaterial::from(Color::BLACK));
-    
+
+    // Load new chunks, reusing the cached material instead of adding a new
+    // (identical) one on every call.
     for (chunk_q, chunk_r) in required_chunks {
-        if !loaded_chunks.chunks.contains(&(chunk_q, chunk_r)) {
-            load_chunk(
-                &mut commands,
-                chunk_q,
-                chunk_r,
-                &mesh_handle,
-                &smaller_mesh_handle,
-                &grass_material,
-                &border_material,
-            );
-            loaded_chunks.chunks.insert((chunk_q, chunk_r));
+        let key = morton_key(chunk_q, chunk_r);
+        if !loaded_chunks.entities.contains_key(&key) {
+            let entity = spawn_chunk(&mut commands, &mut meshes, &game_assets.chunk_material, chunk_q, chunk_r);
+            loaded_chunks.entities.insert(key, entity);
         }
     }
 }
@@ -408,18 +830,45 @@ fn setup_ui(mut commands: Commands) {
         },
         ChunkDisplay,
     ));
+
+    commands.spawn((
+        Text::new(format!("Stamina: {0}/{0}", MAX_STAMINA as i32)),
+        TextFont {
+            font_size: 24.0,
+            ..default()
+        },
+        TextColor(Color::WHITE),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            right: Val::Px(10.0),
+            ..default()
+        },
+        StaminaDisplay,
+    ));
 }
 
 fn update_chunk_display(
-    player_pos: Res<PlayerPosition>,
+    local_player_query: Query<&PlayerPosition, (With<LocalPlayer>, Changed<PlayerPosition>)>,
     mut chunk_display_query: Query<&mut Text, With<ChunkDisplay>>,
 ) {
-    if player_pos.is_changed() {
+    if let Ok(player_pos) = local_player_query.get_single() {
         let player_chunk_q = if player_pos.q >= 0 { player_pos.q / CHUNK_SIZE } else { (player_pos.q - CHUNK_SIZE + 1) / CHUNK_SIZE };
         let player_chunk_r = if player_pos.r >= 0 { player_pos.r / CHUNK_SIZE } else { (player_pos.r - CHUNK_SIZE + 1) / CHUNK_SIZE };
-        
+
         if let Ok(mut text) = chunk_display_query.get_single_mut() {
             **text = format!("Chunk: ({}, {})", player_chunk_q, player_chunk_r);
         }
     }
+}
+
+fn update_stamina_display(
+    local_player_query: Query<&Stamina, (With<LocalPlayer>, Changed<Stamina>)>,
+    mut stamina_display_query: Query<&mut Text, With<StaminaDisplay>>,
+) {
+    if let Ok(stamina) = local_player_query.get_single() {
+        if let Ok(mut text) = stamina_display_query.get_single_mut() {
+            **text = format!("Stamina: {}/{}", stamina.current as i32, stamina.max as i32);
+        }
+    }
 }
\ No newline at end of file